@@ -1,42 +1,104 @@
 use anyhow::bail;
 use anyhow::{Context, Error, Result};
+use mailparse::MailHeaderMap;
 use native_tls::{TlsConnector, TlsStream};
-use rayon::iter::{ParallelIterator, IntoParallelRefIterator};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use notify_rust::Notification;
 use serde::Deserialize;
 use toml::Value;
 
 use std::collections::{HashMap, HashSet};
 use std::net::TcpStream;
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
 use std::time::Duration;
 use std::thread;
 use std::borrow::Cow;
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum AuthMethod {
+    Password,
+    Xoauth2,
+}
+
+impl Default for AuthMethod {
+    fn default() -> Self { AuthMethod::Password }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
 struct Account<'a> {
+    // The account's key in imapnotify.toml, filled in by configure() after
+    // deserializing since the key lives in the enclosing map, not the table
+    // itself; used to title notifications instead of the bare host.
+    #[serde(skip)]
+    name: Cow<'a, str>,
     host: Cow<'a, str>,
     #[serde(default = "Account::default_port")]
     port: u16,
     #[serde(default = "Account::default_starttls")]
     starttls: bool,
     username: Cow<'a, str>,
+    #[serde(default)]
     password: Cow<'a, str>,
+    // Authentication mechanism to log in with; "xoauth2" is for providers
+    // (Gmail, Outlook) that have disabled plain password auth.
+    #[serde(default)]
+    auth: AuthMethod,
+    access_token: Option<Cow<'a, str>>,
     on_new_mail: Cow<'a, str>,
     on_new_mail_post: Option<Cow<'a, str>>,
+    // How often (in seconds) to fire the hooks even without new mail, as a
+    // fallback for servers that silently drop IDLE. Off by default.
+    interval: Option<u64>,
+    // Emit a desktop notification for new mail instead of hand-rolling a
+    // notify-send call in on_new_mail. Off by default.
+    #[serde(default)]
+    notify: bool,
+    // Gotify-style push endpoint to POST new-mail notifications to, for
+    // mobile push without a local hook. If push_url is unset, both push_url
+    // and push_token are taken from the top-level ones in Config (replacing
+    // any per-account push_token), so a single endpoint can cover every
+    // account without repeating it per-account.
+    push_url: Option<Cow<'a, str>>,
+    push_token: Option<Cow<'a, str>>,
     #[serde(borrow)]
     boxes: Cow<'a, [Cow<'a, str>]>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 struct Config<'b> {
+    // Global push_url/push_token, used by any account that doesn't set its
+    // own. Matched before the flatten below claims the rest of the
+    // top-level keys as accounts.
+    #[serde(default)]
+    push_url: Option<Cow<'b, str>>,
+    #[serde(default)]
+    push_token: Option<Cow<'b, str>>,
     #[serde(borrow)]
     #[serde(flatten)]
     accounts: HashMap<String, Account<'b>>,
 }
 
+// A connection's session is either a live IMAP session or the error that
+// last kept it from being one; idle_loop/run reconnect lazily out of the
+// latter instead of the whole Connection being torn down and rebuilt.
+enum SessionState {
+    Online(imap::Session<TlsStream<TcpStream>>),
+    Offline(imap::error::Error),
+}
+
 struct Connection<'c, 'a: 'c> {
     account: &'c Account<'a>,
-    session: imap::Session<TlsStream<TcpStream>>,
+    session: SessionState,
+    // Serializes the on_new_mail/on_new_mail_post hooks between the IDLE
+    // path and the interval timer thread, so a scheduled run and a
+    // push-triggered run never spawn concurrently and race on the mailbox.
+    lock: Arc<Mutex<()>>,
+    // Flipped off by main's config-reload loop to ask a connection whose
+    // account was removed or edited to shut down after its current IDLE.
+    enabled: Arc<AtomicBool>,
 }
 
 impl<'a> Account<'a> {
@@ -45,97 +107,404 @@ impl<'a> Account<'a> {
 
 }
 
-        let tls = TlsConnector::builder().build()?;
-impl<'a: 'b, 'b> Connection<'a, 'b> {
-    fn new<'c: 'a>(account: &'a Account<'a>) -> Result<Connection<'c, 'a>, imap::error::Error> {
+// Envelope metadata for the most recent new message, exposed to the hooks
+// as IMAPNOTIFY_* environment variables so they can know who mailed
+// without having to go re-fetch it themselves.
+struct MailInfo {
+    from: String,
+    subject: String,
+    date: String,
+    mailbox: String,
+    count: usize,
+}
 
-        let client = if account.starttls {
-            imap::connect_insecure((&*account.host, account.port))?.secure(&*account.host, &tls)?
-        } else {
-            imap::connect((&*account.host, account.port), &*account.host, &tls)?
-        }; // I considered putting a check to allow unencrypted connections here, but... why?
+impl MailInfo {
+    fn apply_env(&self, cmd: &mut Command) {
+        cmd.env("IMAPNOTIFY_FROM", &self.from)
+            .env("IMAPNOTIFY_SUBJECT", &self.subject)
+            .env("IMAPNOTIFY_DATE", &self.date)
+            .env("IMAPNOTIFY_MAILBOX", &self.mailbox)
+            .env("IMAPNOTIFY_COUNT", self.count.to_string());
+    }
+}
 
-        let mut session = client.login(account.username.trim(), account.password.trim()).map_err(|(e, _)| e)?;
-        let cap = session.capabilities()?;
+fn run_command(command: &str, mail: Option<&MailInfo>) -> std::io::Result<std::process::ExitStatus> {
+    let mut cmd = Command::new("/bin/sh");
+    cmd.arg("-c").arg(command);
 
-        if !cap.iter().any(|&c| c == "IDLE") {
-            return Err(imap::error::Error::Bad(cap.iter().cloned().collect()));
+    if let Some(mail) = mail {
+        mail.apply_env(&mut cmd);
+    }
+
+    cmd.status()
+}
+
+// Emits a desktop notification summarizing new mail, for accounts with
+// `notify = true` set instead of (or alongside) an on_new_mail hook.
+fn notify_desktop(account: &Account, info: &MailInfo) {
+    let summary = format!("New mail on {} ({})", account.name, info.mailbox);
+    let body = format!("{}\n{}", info.from, info.subject);
+
+    if let Err(e) = Notification::new().summary(&summary).body(&body).show() {
+        eprintln!("Desktop notification failed: {}", e);
+    }
+}
+
+#[derive(serde::Serialize)]
+struct PushMessage<'a> {
+    title: &'a str,
+    message: &'a str,
+    priority: u8,
+}
+
+// How long to wait on a push notification POST before giving up; without a
+// cap a hung Gotify endpoint would block the IDLE thread that calls
+// push_notify indefinitely.
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Shared client for push_notify, built once with PUSH_TIMEOUT rather than
+// per-call so a slow endpoint can't even pay for a fresh connection setup
+// each time.
+fn push_client() -> &'static reqwest::blocking::Client {
+    static CLIENT: std::sync::OnceLock<reqwest::blocking::Client> = std::sync::OnceLock::new();
+
+    CLIENT.get_or_init(|| {
+        reqwest::blocking::Client::builder()
+            .timeout(PUSH_TIMEOUT)
+            .build()
+            .expect("failed to build push notification HTTP client")
+    })
+}
+
+// POSTs a Gotify-style push notification for new mail. Failures (including a
+// timed-out or hung endpoint) are logged like a failed hook command and never
+// propagate, so a flaky push server can't take down the IDLE loop.
+fn push_notify(account: &Account, info: &MailInfo) {
+    let url = match &account.push_url {
+        Some(url) => &**url,
+        None => return,
+    };
+
+    let body = PushMessage {
+        title: &format!("New mail on {} ({})", account.name, info.mailbox),
+        message: &format!("{}\n{}", info.from, info.subject),
+        priority: 5,
+    };
+
+    let mut request = push_client().post(url);
+
+    if let Some(token) = &account.push_token {
+        request = request.query(&[("token", &**token)]);
+    }
+
+    match request.json(&body).send().and_then(|r| r.error_for_status()) {
+        Ok(_) => (),
+        Err(e) => eprintln!("Push notification failed: {}", e),
+    }
+}
+
+// Runs on_new_mail, then on_new_mail_post if it's set, holding `lock` for
+// the duration so the two hook paths can't overlap. Waits for each child to
+// be reaped before releasing the lock.
+fn run_hooks(lock: &Mutex<()>, command: &str, command_post: Option<&str>, mail: Option<&MailInfo>) {
+    let _guard = lock.lock().unwrap();
+
+    match run_command(command, mail) {
+        Err(e) => eprintln!("Command failed: {}", e),
+        Ok(_) => if let Some(command) = command_post {
+            if let Err(e) = run_command(command, mail) {
+                eprintln!("Command failed: {}", e);
+            }
+        },
+    }
+}
+
+// Fetches and parses the headers of `uid` in the currently-examined mailbox
+// `mbox`, for exposing to the hooks as IMAPNOTIFY_* environment variables.
+// Returns Ok(None) rather than an error if the headers can't be parsed, so
+// a malformed message doesn't stop the hooks from running at all.
+fn fetch_mail_info(session: &mut imap::Session<TlsStream<TcpStream>>, uid: u32, mbox: &str) -> Result<Option<MailInfo>> {
+    let fetch = session.uid_fetch(uid.to_string(), "RFC822.HEADER")?;
+
+    let header = match fetch.iter().next().and_then(|message| message.header()) {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+
+    let parsed = match mailparse::parse_mail(header) {
+        Ok(parsed) => parsed,
+        Err(_) => return Ok(None),
+    };
+
+    Ok(Some(MailInfo {
+        from: parsed.headers.get_first_value("From").unwrap_or_default(),
+        subject: parsed.headers.get_first_value("Subject").unwrap_or_default(),
+        date: parsed.headers.get_first_value("Date").unwrap_or_default(),
+        mailbox: mbox.to_string(),
+        count: 0, // filled in by the caller once the total is known.
+    }))
+}
+
+// SASL authenticator for XOAUTH2, used in place of a plain login for
+// providers that have disabled password auth (Gmail, Outlook).
+struct XOAuth2Authenticator<'a> {
+    user: &'a str,
+    token: &'a str,
+}
+
+impl<'a> imap::Authenticator for XOAuth2Authenticator<'a> {
+    type Response = String;
+
+    fn process(&self, _challenge: &[u8]) -> Self::Response {
+        format!("user={}\x01auth=Bearer {}\x01\x01", self.user, self.token)
+    }
+}
+
+// Performs the TCP/TLS handshake, login, and IDLE capability/mailbox check
+// for an account. Shared by Connection::new and the lazy-reconnect path so
+// both establish a session the same way.
+fn establish<'a>(account: &Account<'a>) -> Result<imap::Session<TlsStream<TcpStream>>, imap::error::Error> {
+    let tls = TlsConnector::builder().build()?;
+
+    let client = if account.starttls {
+        imap::connect_insecure((&*account.host, account.port))?.secure(&*account.host, &tls)?
+    } else {
+        imap::connect((&*account.host, account.port), &*account.host, &tls)?
+    }; // I considered putting a check to allow unencrypted connections here, but... why?
+
+    let mut session = match account.auth {
+        AuthMethod::Password => {
+            client.login(account.username.trim(), account.password.trim()).map_err(|(e, _)| e)?
+        }
+        AuthMethod::Xoauth2 => {
+            let token = account.access_token.as_deref().unwrap_or("").trim();
+            let auth = XOAuth2Authenticator { user: account.username.trim(), token };
+            client.authenticate("XOAUTH2", &auth).map_err(|(e, _)| e)?
         }
+    };
+
+    let cap = session.capabilities()?;
 
-        session.examine(&account.boxes[0])?;
+    if !cap.iter().any(|&c| c == "IDLE") {
+        return Err(imap::error::Error::Bad(cap.iter().cloned().collect()));
+    }
+
+    session.examine(&account.boxes[0])?;
+
+    Ok(session)
+}
+
+// How often the interval timer wakes to recheck `alive` while otherwise
+// sleeping; small enough that idle_loop's teardown isn't noticeably delayed
+// when the IDLE side errors out mid-interval.
+const ALIVE_POLL: Duration = Duration::from_millis(200);
+
+// Sleeps for `duration`, but in short increments so the thread notices
+// `alive` flipping false promptly instead of sleeping through a whole
+// interval before idle_loop can return and reconnect.
+fn sleep_while_alive(alive: &AtomicBool, duration: Duration) {
+    let mut remaining = duration;
+
+    while alive.load(Ordering::SeqCst) && !remaining.is_zero() {
+        let step = remaining.min(ALIVE_POLL);
+        thread::sleep(step);
+        remaining -= step;
+    }
+}
+
+impl<'a: 'b, 'b> Connection<'a, 'b> {
+    // Always succeeds, even when the server is unreachable right now: a
+    // failed handshake/login is kept as SessionState::Offline so the
+    // account stays in main's supervision set and is retried lazily.
+    fn new<'c: 'a>(account: &'a Account<'a>) -> Connection<'c, 'a> {
+        let session = match establish(account) {
+            Ok(session) => SessionState::Online(session),
+            Err(e) => SessionState::Offline(e),
+        };
 
-        Ok(Connection {
+        Connection {
             account: &account,
             session,
-        })
+            lock: Arc::new(Mutex::new(())),
+            enabled: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    fn logout(&mut self) {
+        if let SessionState::Online(session) = &mut self.session {
+            let _ = session.logout(); // This will probably fail, so ignore any error.
+        }
     }
 
-    fn idle_loop(&mut self) -> Result<()> {
+    // `progressed` is flipped once a full IDLE cycle (examine, search, any
+    // hooks, idle+wait_keepalive) completes, so callers can tell a
+    // transient drop after real uptime apart from a connection that never
+    // got anywhere (e.g. a bad mailbox name or IDLE lost right after login).
+    fn idle_loop(&mut self, progressed: &AtomicBool) -> Result<()> {
         let mut last = 0;
         let command = &*self.account.on_new_mail;
         let command_post = self.account.on_new_mail_post.as_deref();
+        let interval = self.account.interval;
+        let lock = &self.lock;
+        let alive = AtomicBool::new(true);
+        let account = self.account;
+        let session = &mut self.session;
+        let boxes = &*self.account.boxes;
+        let enabled = &self.enabled;
+
+        let result = crossbeam::scope(|s| {
+            if let Some(interval) = interval {
+                let alive = &alive;
+                s.spawn(move |_| {
+                    while alive.load(Ordering::SeqCst) {
+                        sleep_while_alive(&alive, Duration::from_secs(interval));
+
+                        if !alive.load(Ordering::SeqCst) {
+                            break;
+                        }
 
-        loop {
-            let mut uids = HashSet::new();
-
-            for mbox in &*self.account.boxes {
-                self.session.examine(mbox)?;
-                let search = self.session.uid_search("NEW 1:*")?;
-                uids.extend(search);
+                        run_hooks(lock, command, command_post, None);
+                    }
+                });
             }
 
-            if uids.iter().all(|&uid| uid > last) {
-                // New mail, let's run!
-                let scope = crossbeam::scope(|s| {
-                    s.spawn(move |_| {
-                        if let Err(e) = Command::new("/bin/sh").arg("-c").arg(command).spawn() {
-                            eprintln!("Command failed: {}", e);
-                        } else if let Some(command) = command_post {
-                            if let Err(e) = Command::new("/bin/sh").arg("-c").arg(command).spawn() {
-                                eprintln!("Command failed: {}", e);
+            let result = (|| -> Result<()> {
+                loop {
+                    if !enabled.load(Ordering::SeqCst) {
+                        // The config-reload loop retired this account; let
+                        // the thread wind down instead of reconnecting.
+                        return Ok(());
+                    }
+
+                    if let SessionState::Offline(_) = session {
+                        *session = match establish(account) {
+                            Ok(s) => SessionState::Online(s),
+                            Err(e) => SessionState::Offline(e),
+                        };
+                    }
+
+                    let imap_session = match session {
+                        SessionState::Online(s) => s,
+                        SessionState::Offline(e) => bail!("{}", e),
+                    };
+
+                    let mut uids = HashSet::new();
+                    let mut new_count = 0usize;
+                    // UIDs are only unique within a single mailbox, so the
+                    // newest message across boxes can't be chosen by
+                    // comparing UIDs directly; compare by parsed Date
+                    // instead. Undated/unparseable messages sort first
+                    // rather than winning a tie by virtue of mailbox order.
+                    let mut latest: Option<(i64, MailInfo)> = None;
+
+                    for mbox in boxes {
+                        imap_session.examine(mbox)?;
+                        let search = imap_session.uid_search("NEW 1:*")?;
+
+                        // Counted per mailbox rather than via the `uids` set
+                        // below, since UIDs that happen to collide between
+                        // mailboxes would otherwise dedup away real messages.
+                        new_count += search.len();
+
+                        if let Some(&uid) = search.iter().max() {
+                            if let Some(info) = fetch_mail_info(imap_session, uid, mbox)? {
+                                let when = mailparse::dateparse(&info.date).unwrap_or(i64::MIN);
+
+                                if latest.as_ref().map_or(true, |&(seen, _)| when >= seen) {
+                                    latest = Some((when, info));
+                                }
                             }
                         }
-                    });
-                });
 
-                if let Err(any) = scope {
-                    match any.downcast::<Error>() {
-                        Ok(error) => return Err(*error),
-                        Err(any)  => bail!("unexpected threading error: {:?}", any),
+                        uids.extend(search);
                     }
-                };
-            } else {
-                uids.clear();
-            }
 
-            last = std::cmp::max(last, uids.iter().cloned().max().unwrap_or(0));
+                    if uids.iter().all(|&uid| uid > last) {
+                        // New mail, let's run! The envelope may be missing
+                        // (header fetch came back empty or didn't parse) even
+                        // though mail did arrive, so degrade to an empty
+                        // MailInfo rather than skipping notify/push/hooks.
+                        let mut info = latest.map_or_else(
+                            || MailInfo {
+                                from: String::new(),
+                                subject: String::new(),
+                                date: String::new(),
+                                mailbox: String::new(),
+                                count: 0,
+                            },
+                            |(_, info)| info,
+                        );
+                        info.count = new_count;
+
+                        if account.notify {
+                            notify_desktop(account, &info);
+                        }
+
+                        if account.push_url.is_some() {
+                            push_notify(account, &info);
+                        }
+
+                        run_hooks(lock, command, command_post, Some(&info));
+                    } else {
+                        uids.clear();
+                    }
 
-            self.session.idle()?.wait_keepalive()?;
+                    last = std::cmp::max(last, uids.iter().cloned().max().unwrap_or(0));
+
+                    imap_session.idle()?.wait_keepalive()?;
+                    progressed.store(true, Ordering::SeqCst);
+                }
+            })();
+
+            alive.store(false, Ordering::SeqCst);
+            result
+        });
+
+        match result {
+            Ok(result) => result,
+            Err(any) => match any.downcast::<Error>() {
+                Ok(error) => Err(*error),
+                Err(any)  => bail!("unexpected threading error: {:?}", any),
+            },
         }
     }
 
     fn run(&mut self) {
+        let mut wait = 1;
+
         loop {
-            if let Err(e) = self.idle_loop() {
-                eprintln!("Connection to {} failed: {}.", self.account.host, e);
-                let _ = self.session.logout(); // This will probably fail, so ignore any error.
-                break;
-            }
-        }
+            let progressed = AtomicBool::new(false);
 
-        let mut wait = 1;
-        for _try in 0..5 {
-            match Connection::new(self.account) {
-                Err(e) => {
-                    eprintln!("Connection to {} failed: {}. Retrying in {} seconds.", self.account.host, e, wait);
-                    thread::sleep(Duration::from_secs(wait));
-                    wait *= 2;
+            match self.idle_loop(&progressed) {
+                Ok(()) => {
+                    self.logout();
+                    return;
                 }
-                Ok(mut c) => {
-                    eprintln!("Connection for {} reestablished.", self.account.host);
-                    return c.run();
+                Err(e) => {
+                    eprintln!("Connection to {} failed: {}.", self.account.host, e);
+
+                    // Only forgive the backoff if idle_loop actually completed
+                    // a full IDLE cycle before failing again; a post-connect
+                    // failure (bad mailbox, IDLE lost right after login)
+                    // never reaches that point, and letting the session
+                    // having been Online reset wait would turn a persistent
+                    // failure like that into a 1-second hot-retry loop.
+                    if progressed.load(Ordering::SeqCst) {
+                        wait = 1;
+                    }
+
+                    self.logout();
+                    self.session = SessionState::Offline(imap::error::Error::Bad(e.to_string().into_bytes()));
                 }
             }
+
+            if !self.enabled.load(Ordering::SeqCst) {
+                return;
+            }
+
+            eprintln!("Retrying connection to {} in {} seconds.", self.account.host, wait);
+            thread::sleep(Duration::from_secs(wait));
+            wait = (wait * 2).min(64);
         }
     }
 
@@ -152,7 +521,7 @@ fn preprocess_toml(t: &mut Value) {
         preprocess_toml(v)
     };
 
-    if dbg!(table.contains_key("password_eval")) {
+    if table.contains_key("password_eval") {
         let value    = table.remove("password_eval").unwrap();
         let fallback = table.remove("password");
 
@@ -174,63 +543,169 @@ fn preprocess_toml(t: &mut Value) {
 
         password.and_then(|v| table.insert("password".to_string(), v));
     };
+
+    if table.contains_key("token_eval") {
+        let value    = table.remove("token_eval").unwrap();
+        let fallback = table.remove("access_token");
+
+        let token = if let Some(eval) = value.as_str() {
+            match Command::new("/bin/sh").arg("-c").arg(eval).output() {
+                Err(e) => { eprintln!("Token eval failed: {}", e); None },
+                Ok(child) => match std::str::from_utf8(&child.stdout) {
+                    Err(e) => { eprintln!("Token eval failed: {}", e); None },
+                    Ok(string) => Some(Value::from(string)),
+                }
+            }
+        } else {
+            fallback
+        };
+
+        token.and_then(|v| table.insert("access_token".to_string(), v));
+    };
 }
 
-fn configure<'a>() -> Result<Vec<Account<'a>>> {
+fn config_path() -> Result<std::path::PathBuf> {
     let xdg = xdg::BaseDirectories::new()?;
-    let path = xdg.find_config_file("imapnotify.toml").context("file not found")?;
+    xdg.find_config_file("imapnotify.toml").context("file not found")
+}
+
+fn configure(path: &std::path::Path) -> Result<HashMap<String, Account<'static>>> {
     let file = std::fs::read_to_string(path)?;
 
-    let mut toml: Value = toml::from_str(&file)?;
+    // Leak the file contents so the borrowed Cow<str> fields in Account can
+    // outlive this function; imapnotify only reloads on an edit to the
+    // config file, so leaking once per edit is a fine trade against making
+    // every field an owned String.
+    let file: &'static str = Box::leak(file.into_boxed_str());
+
+    let mut toml: Value = toml::from_str(file)?;
     preprocess_toml(&mut toml);
-    let config = toml.try_into::<Config>()?;
+    let mut config = toml.try_into::<Config<'static>>()?;
 
     if config.accounts.is_empty() {
         bail!("no accounts in imapnotify.toml");
     }
 
-    let accounts = config.accounts.into_iter().map(|(_k,v)|v).collect();
+    for (key, account) in config.accounts.iter_mut() {
+        account.name = Cow::Owned(key.clone());
 
-    Ok(accounts)
+        if account.push_url.is_none() {
+            account.push_url = config.push_url.clone();
+            account.push_token = config.push_token.clone();
+        }
+    }
+
+    Ok(config.accounts)
 }
 
-fn main() -> Result<()> {
-    let config = configure().context("Could not process configuration file imapnotify.toml")?;
+// A connection thread spawned for one account key, along with the switch
+// main's reload loop flips to ask it to shut down.
+struct Managed {
+    enabled: Arc<AtomicBool>,
+    handle: thread::JoinHandle<()>,
+}
 
-    let connections: Vec<_> = config.par_iter().filter_map(|account| {
-        let mut wait = 1;
-        for _try in 0..5 {
-            match Connection::new(account) {
-                Ok(c) => return Some(c),
-                Err(e) => {
-                    eprintln!("Connection to {} failed: {}. Retrying in {} seconds.", account.host, e, wait);
-                    thread::sleep(Duration::from_secs(wait));
-                    wait *= 2;
-                }
-            }
-        }
+// Connection::new never fails outright, so this always produces a running
+// connection thread; one whose account is unreachable right now just
+// starts out offline and keeps retrying lazily from inside run().
+fn spawn_connection(account: &'static Account<'static>) -> Managed {
+    let enabled = Arc::new(AtomicBool::new(true));
+    let enabled_thread = Arc::clone(&enabled);
+
+    let handle = thread::spawn(move || {
+        let mut connection = Connection::new(account);
+        connection.enabled = enabled_thread;
+        connection.run();
+    });
 
-        None // tries exceeded.
-    }).collect();
+    Managed { enabled, handle }
+}
 
-    if connections.is_empty() {
-        bail!("could not establish any connections");
-    }
+// Tears down connections for removed or changed accounts and spawns fresh
+// ones for added or changed accounts; connections for accounts that didn't
+// change are left running untouched.
+fn reload(path: &std::path::Path, accounts: &mut HashMap<String, Account<'static>>, connections: &mut HashMap<String, Managed>) {
+    let new_accounts = match configure(path) {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            eprintln!("Could not reload {}: {}.", path.display(), e);
+            return;
+        }
+    };
 
-    let scope = crossbeam::scope(move |s| {
-        for mut connection in connections {
-            s.spawn(move |_| {
-                connection.run();
+    let changed: Vec<String> = accounts.iter()
+        .filter(|(key, account)| new_accounts.get(*key) != Some(account))
+        .map(|(key, _)| key.clone())
+        .collect();
+
+    for key in &changed {
+        if let Some(managed) = connections.remove(key) {
+            managed.enabled.store(false, Ordering::SeqCst);
+
+            // A retired connection only notices `enabled` going false at the
+            // top of its loop, which can be tens of minutes away if it's
+            // blocked in an IDLE keepalive wait; join it from a throwaway
+            // thread instead of blocking the config watch loop on it.
+            thread::spawn(move || {
+                let _ = managed.handle.join();
             });
         }
-    });
+    }
 
-    if let Err(any) = scope {
-        match any.downcast::<Error>() {
-            Ok(error) => return Err(*error),
-            Err(any)  => bail!("unexpected threading error: {:?}", any),
+    for (key, account) in &new_accounts {
+        if changed.contains(key) || !accounts.contains_key(key) {
+            let account: &'static Account<'static> = Box::leak(Box::new(account.clone()));
+            connections.insert(key.clone(), spawn_connection(account));
         }
-    };
+    }
+
+    *accounts = new_accounts;
+}
+
+fn main() -> Result<()> {
+    let path = config_path().context("Could not find configuration file imapnotify.toml")?;
+    let accounts = configure(&path).context("Could not process configuration file imapnotify.toml")?;
+
+    let mut connections: HashMap<String, Managed> = HashMap::new();
+
+    for (key, account) in &accounts {
+        let account: &'static Account<'static> = Box::leak(Box::new(account.clone()));
+        connections.insert(key.clone(), spawn_connection(account));
+    }
+
+    // Every configured account stays supervised even if it's unreachable
+    // right now; each Connection retries its own session lazily.
+    let mut accounts = accounts;
+
+    // Watch the config file's directory rather than the file itself: editors
+    // commonly save via write-to-temp-then-rename, which replaces the
+    // watched inode and would leave a direct file watch dead after the
+    // first such save. We filter directory events down to ones that
+    // mention our filename below.
+    let watch_dir = path.parent().context("Could not determine config file's directory")?;
+    let file_name = path.file_name().context("Could not determine config file name")?.to_owned();
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher: RecommendedWatcher = notify::recommended_watcher(tx)?;
+    watcher.watch(watch_dir, RecursiveMode::NonRecursive)?;
+
+    for event in rx {
+        match event {
+            Ok(event) if touches_config(&event, &file_name) => reload(&path, &mut accounts, &mut connections),
+            Ok(_) => {}
+            Err(e) => eprintln!("Config watch error: {}", e),
+        }
+    }
 
     Ok(())
 }
+
+// Matches any event that touches our config file, not just a direct Modify:
+// a write-to-temp-then-rename save surfaces as a Remove of the old inode
+// followed by a Create of the new one rather than a Modify. `reload` already
+// tolerates a transient Remove by logging and skipping if the file isn't
+// there yet, so it's safe to react to any of these and let the next event
+// pick the reload back up.
+fn touches_config(event: &notify::Event, file_name: &std::ffi::OsStr) -> bool {
+    !event.kind.is_access() && event.paths.iter().any(|p| p.file_name() == Some(file_name))
+}